@@ -0,0 +1,310 @@
+/*
+ * Copyright 2017 Bitwise IO, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use protobuf::Message as M;
+use protobuf::repeated::RepeatedField;
+
+use messages::validator::Message_MessageType;
+use messages::processor::TpProcessRequest;
+use messages::events::Event;
+use messages::events::Event_Attribute;
+use messages::state_context::TpStateEntry;
+use messages::state_context::TpStateGetRequest;
+use messages::state_context::TpStateGetResponse;
+use messages::state_context::TpStateGetResponse_Status;
+use messages::state_context::TpStateSetRequest;
+use messages::state_context::TpStateSetResponse;
+use messages::state_context::TpStateSetResponse_Status;
+use messages::state_context::TpStateDeleteRequest;
+use messages::state_context::TpStateDeleteResponse;
+use messages::state_context::TpStateDeleteResponse_Status;
+use messages::state_context::TpEventAddRequest;
+use messages::state_context::TpEventAddResponse;
+use messages::state_context::TpEventAddResponse_Status;
+use messages::state_context::TpReceiptAddDataRequest;
+use messages::state_context::TpReceiptAddDataResponse;
+use messages::state_context::TpReceiptAddDataResponse_Status;
+
+use messaging::stream::MessageSender;
+use messaging::zmq_stream::ZmqMessageSender;
+
+use super::generate_correlation_id;
+
+/// Errors that a `TransactionHandler` may return from `apply`, and that the
+/// `TransactionContext` surfaces when it cannot talk to the validator.
+#[derive(Debug)]
+pub enum ApplyError {
+    /// The transaction is invalid and should be rejected; the message is
+    /// returned to the client.
+    InvalidTransaction(String),
+    /// Something went wrong that is not the transaction's fault (e.g. the
+    /// state round-trip failed).
+    InternalError(String),
+}
+
+impl Error for ApplyError {
+    fn description(&self) -> &str {
+        match *self {
+            ApplyError::InvalidTransaction(ref msg) => msg,
+            ApplyError::InternalError(ref msg) => msg,
+        }
+    }
+}
+
+impl fmt::Display for ApplyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ApplyError::InvalidTransaction(ref msg) =>
+                write!(f, "InvalidTransaction: {}", msg),
+            ApplyError::InternalError(ref msg) =>
+                write!(f, "InternalError: {}", msg),
+        }
+    }
+}
+
+/// Implemented by each transaction family to validate and apply transactions.
+pub trait TransactionHandler {
+    /// The name of the transaction family this handler serves.
+    fn family_name(&self) -> String;
+    /// The family versions this handler can process.
+    fn family_versions(&self) -> Vec<String>;
+    /// The state namespace prefixes this handler reads and writes.
+    fn namespaces(&self) -> Vec<String>;
+    /// Validates and applies a single transaction, reading and writing state
+    /// through the supplied context.
+    fn apply(&self, request: &TpProcessRequest,
+             context: &mut TransactionContext) -> Result<(), ApplyError>;
+}
+
+/// The handle a `TransactionHandler` uses to read and write global state and
+/// to emit events while applying a transaction. It holds the context id the
+/// validator assigned to the transaction and a sender pointed back at that
+/// validator, so every call is a request/response round-trip on the ZMQ
+/// stream.
+pub struct TransactionContext {
+    context_id: String,
+    sender: ZmqMessageSender,
+}
+
+impl TransactionContext {
+    /// Creates a context for the transaction identified by `context_id`,
+    /// sending requests on `sender`.
+    ///
+    /// # Arguments
+    ///
+    /// * context_id - the validator-assigned id scoping the state reads/writes
+    /// * sender - a sender connected to the validator, cloned per request so
+    ///   it can be moved onto a worker thread
+    pub fn new(context_id: &str, sender: ZmqMessageSender) -> TransactionContext {
+        TransactionContext {
+            context_id: String::from(context_id),
+            sender: sender,
+        }
+    }
+
+    /// Serializes `request`, sends it to the validator as `message_type`,
+    /// blocks on the reply and returns its content bytes, mapping any failure
+    /// to an `ApplyError::InternalError`.
+    fn send<T: M>(&mut self, message_type: Message_MessageType,
+                  request: &T) -> Result<Vec<u8>, ApplyError> {
+        let serialized = request.write_to_bytes()
+            .map_err(|err| ApplyError::InternalError(
+                format!("Failed to serialize request: {}", err.description())))?;
+
+        let mut future = self.sender.send(
+                message_type, &generate_correlation_id(), &serialized)
+            .map_err(|err| ApplyError::InternalError(
+                format!("Failed to send request: {}", err.description())))?;
+
+        let response = future.get()
+            .map_err(|err| ApplyError::InternalError(
+                format!("Failed to receive response: {}", err.description())))?;
+
+        Ok(Vec::from(response.get_content()))
+    }
+
+    /// Reads the values stored at `addresses`, returning one `(address, value)`
+    /// pair for every entry the validator includes in its response, passed
+    /// through without additional filtering. The validator omits addresses
+    /// that have no value set, so unset addresses simply do not appear in the
+    /// returned vector.
+    pub fn get_state(&mut self, addresses: &[String])
+        -> Result<Vec<(String, Vec<u8>)>, ApplyError> {
+        let mut request = TpStateGetRequest::new();
+        request.set_context_id(self.context_id.clone());
+        request.set_addresses(RepeatedField::from_vec(addresses.to_vec()));
+
+        let content = self.send(
+            Message_MessageType::TP_STATE_GET_REQUEST, &request)?;
+        let response: TpStateGetResponse = ::protobuf::parse_from_bytes(&content)
+            .map_err(|err| ApplyError::InternalError(
+                format!("Failed to parse TpStateGetResponse: {}",
+                        err.description())))?;
+
+        match response.get_status() {
+            TpStateGetResponse_Status::OK => Ok(response.get_entries()
+                .iter()
+                .map(|entry| (String::from(entry.get_address()),
+                              Vec::from(entry.get_data())))
+                .collect()),
+            TpStateGetResponse_Status::AUTHORIZATION_ERROR =>
+                Err(ApplyError::InvalidTransaction(format!(
+                    "Tried to get unauthorized addresses: {:?}", addresses))),
+            TpStateGetResponse_Status::STATUS_UNSET =>
+                Err(ApplyError::InternalError(String::from(
+                    "Status unset on TpStateGetResponse"))),
+        }
+    }
+
+    /// Writes each `(address, value)` entry to global state, returning the
+    /// addresses the validator confirmed were set.
+    pub fn set_state(&mut self, entries: HashMap<String, Vec<u8>>)
+        -> Result<Vec<String>, ApplyError> {
+        let state_entries: Vec<TpStateEntry> = entries.into_iter()
+            .map(|(address, data)| {
+                let mut entry = TpStateEntry::new();
+                entry.set_address(address);
+                entry.set_data(data);
+                entry
+            })
+            .collect();
+
+        let mut request = TpStateSetRequest::new();
+        request.set_context_id(self.context_id.clone());
+        request.set_entries(RepeatedField::from_vec(state_entries));
+
+        let content = self.send(
+            Message_MessageType::TP_STATE_SET_REQUEST, &request)?;
+        let response: TpStateSetResponse = ::protobuf::parse_from_bytes(&content)
+            .map_err(|err| ApplyError::InternalError(
+                format!("Failed to parse TpStateSetResponse: {}",
+                        err.description())))?;
+
+        match response.get_status() {
+            TpStateSetResponse_Status::OK => Ok(response.get_addresses()
+                .iter()
+                .map(String::from)
+                .collect()),
+            TpStateSetResponse_Status::AUTHORIZATION_ERROR =>
+                Err(ApplyError::InvalidTransaction(String::from(
+                    "Tried to set unauthorized addresses"))),
+            TpStateSetResponse_Status::STATUS_UNSET =>
+                Err(ApplyError::InternalError(String::from(
+                    "Status unset on TpStateSetResponse"))),
+        }
+    }
+
+    /// Deletes the values stored at `addresses`, returning the addresses the
+    /// validator confirmed were deleted.
+    pub fn delete_state(&mut self, addresses: &[String])
+        -> Result<Vec<String>, ApplyError> {
+        let mut request = TpStateDeleteRequest::new();
+        request.set_context_id(self.context_id.clone());
+        request.set_addresses(RepeatedField::from_vec(addresses.to_vec()));
+
+        let content = self.send(
+            Message_MessageType::TP_STATE_DELETE_REQUEST, &request)?;
+        let response: TpStateDeleteResponse = ::protobuf::parse_from_bytes(&content)
+            .map_err(|err| ApplyError::InternalError(
+                format!("Failed to parse TpStateDeleteResponse: {}",
+                        err.description())))?;
+
+        match response.get_status() {
+            TpStateDeleteResponse_Status::OK => Ok(response.get_addresses()
+                .iter()
+                .map(String::from)
+                .collect()),
+            TpStateDeleteResponse_Status::AUTHORIZATION_ERROR =>
+                Err(ApplyError::InvalidTransaction(String::from(
+                    "Tried to delete unauthorized addresses"))),
+            TpStateDeleteResponse_Status::STATUS_UNSET =>
+                Err(ApplyError::InternalError(String::from(
+                    "Status unset on TpStateDeleteResponse"))),
+        }
+    }
+
+    /// Emits a typed event of `event_type` with the given attribute pairs and
+    /// opaque `data`, which clients subscribed to that event type receive.
+    pub fn add_event(&mut self, event_type: String,
+                     attributes: Vec<(String, String)>,
+                     data: &[u8]) -> Result<(), ApplyError> {
+        let event_attributes: Vec<Event_Attribute> = attributes.into_iter()
+            .map(|(key, value)| {
+                let mut attribute = Event_Attribute::new();
+                attribute.set_key(key);
+                attribute.set_value(value);
+                attribute
+            })
+            .collect();
+
+        let mut event = Event::new();
+        event.set_event_type(event_type);
+        event.set_attributes(RepeatedField::from_vec(event_attributes));
+        event.set_data(Vec::from(data));
+
+        let mut request = TpEventAddRequest::new();
+        request.set_context_id(self.context_id.clone());
+        request.set_event(event);
+
+        let content = self.send(
+            Message_MessageType::TP_EVENT_ADD_REQUEST, &request)?;
+        let response: TpEventAddResponse = ::protobuf::parse_from_bytes(&content)
+            .map_err(|err| ApplyError::InternalError(
+                format!("Failed to parse TpEventAddResponse: {}",
+                        err.description())))?;
+
+        match response.get_status() {
+            TpEventAddResponse_Status::OK => Ok(()),
+            TpEventAddResponse_Status::ERROR =>
+                Err(ApplyError::InternalError(String::from(
+                    "Failed to add event"))),
+            TpEventAddResponse_Status::STATUS_UNSET =>
+                Err(ApplyError::InternalError(String::from(
+                    "Status unset on TpEventAddResponse"))),
+        }
+    }
+
+    /// Appends opaque `data` to the transaction receipt, letting the family
+    /// attach arbitrary output a client can read back after the batch commits.
+    pub fn add_receipt_data(&mut self, data: &[u8]) -> Result<(), ApplyError> {
+        let mut request = TpReceiptAddDataRequest::new();
+        request.set_context_id(self.context_id.clone());
+        request.set_data(Vec::from(data));
+
+        let content = self.send(
+            Message_MessageType::TP_RECEIPT_ADD_DATA_REQUEST, &request)?;
+        let response: TpReceiptAddDataResponse =
+            ::protobuf::parse_from_bytes(&content)
+            .map_err(|err| ApplyError::InternalError(
+                format!("Failed to parse TpReceiptAddDataResponse: {}",
+                        err.description())))?;
+
+        match response.get_status() {
+            TpReceiptAddDataResponse_Status::OK => Ok(()),
+            TpReceiptAddDataResponse_Status::ERROR =>
+                Err(ApplyError::InternalError(String::from(
+                    "Failed to add receipt data"))),
+            TpReceiptAddDataResponse_Status::STATUS_UNSET =>
+                Err(ApplyError::InternalError(String::from(
+                    "Status unset on TpReceiptAddDataResponse"))),
+        }
+    }
+}
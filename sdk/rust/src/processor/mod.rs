@@ -22,17 +22,26 @@ extern crate rand;
 use self::rand::Rng;
 
 pub mod handler;
+pub mod protocol;
 
+use std::cmp;
 use std::error::Error;
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::Duration;
 
 use protobuf::Message as M;
 use protobuf::repeated::RepeatedField;
 use messages::validator::Message_MessageType;
 use messages::processor::TpRegisterRequest;
+use messages::processor::TpRegisterResponse;
+use messages::processor::TpUnregisterRequest;
 use messages::processor::TpProcessRequest;
 use messages::processor::TpProcessResponse;
 use messages::processor::TpProcessResponse_Status;
+use messages::network::PingResponse;
 use messaging::stream::MessageConnection;
+use messaging::stream::MessageReceiver;
 use messaging::stream::MessageSender;
 use messaging::zmq_stream::ZmqMessageSender;
 use messaging::stream::SendError;
@@ -43,16 +52,85 @@ use self::handler::TransactionContext;
 use self::handler::TransactionHandler;
 use self::handler::ApplyError;
 
+use self::protocol::ProtocolVersion;
+
 /// Generates a random correlation id for use in Message
 fn generate_correlation_id() -> String {
     const LENGTH: usize = 16;
     rand::thread_rng().gen_ascii_chars().take(LENGTH).collect()
 }
 
+/// Default delay before the first reconnect attempt.
+const DEFAULT_INITIAL_RECONNECT_DELAY: u64 = 100;
+/// Default cap on the reconnect delay.
+const DEFAULT_MAX_RECONNECT_DELAY: u64 = 3000;
+
+/// Truncated exponential backoff with full jitter, used between validator
+/// reconnect attempts so a restarting validator is not hammered and many
+/// processors do not reconnect in lockstep.
+struct Backoff {
+    initial: Duration,
+    max: Duration,
+    current: Duration
+}
+
+impl Backoff {
+    fn new(initial: Duration, max: Duration) -> Backoff {
+        Backoff {
+            initial: initial,
+            max: max,
+            current: initial
+        }
+    }
+
+    /// Resets the backoff to its initial delay, called after a successful
+    /// registration.
+    fn reset(&mut self) {
+        self.current = self.initial;
+    }
+
+    /// Returns the delay to wait before the next attempt, then doubles the
+    /// base delay up to the cap. Full jitter is applied: the returned delay is
+    /// a random point in `[0, base]`.
+    fn next_delay(&mut self) -> Duration {
+        let base = self.current;
+        // Clamp before doubling: a near-`Duration::MAX` max would overflow on
+        // `* 2` and panic, so fall back to the cap when doubling would wrap.
+        self.current = self.current
+            .checked_mul(2)
+            .map(|doubled| cmp::min(self.max, doubled))
+            .unwrap_or(self.max);
+
+        let millis = base.as_millis();
+        let jittered = if millis == 0 {
+            0
+        } else {
+            // Full jitter: a random point in `[0, base]`.
+            let upper = cmp::min(millis, u64::MAX as u128) as u64;
+            rand::thread_rng().gen_range(0, upper.saturating_add(1))
+        };
+        Duration::from_millis(jittered)
+    }
+}
+
+/// A single TP_PROCESS_REQUEST handed from the receive loop to a worker
+/// thread: the parsed request, the correlation id its reply must carry, and a
+/// cloned sender to answer on so replies can complete out of order.
+struct WorkItem {
+    request: TpProcessRequest,
+    correlation_id: String,
+    sender: ZmqMessageSender
+}
+
 pub struct TransactionProcessor<'a> {
     endpoint: String,
     conn: ZmqMessageConnection,
-    handlers: Vec<&'a TransactionHandler>
+    handlers: Vec<&'a (TransactionHandler + Sync)>,
+    max_workers: usize,
+    initial_reconnect_delay: Duration,
+    max_reconnect_delay: Duration,
+    sender: Option<ZmqMessageSender>,
+    protocol_version: ProtocolVersion
 }
 
 impl<'a> TransactionProcessor<'a> {
@@ -60,29 +138,109 @@ impl<'a> TransactionProcessor<'a> {
     /// validator and routing transaction processing requests to a registered
     /// handler. It uses ZMQ and channels to handle requests concurrently.
     pub fn new(endpoint: &str) -> TransactionProcessor {
+        let threads = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        TransactionProcessor::new_with_threads(endpoint, threads)
+    }
+
+    /// Creates a TransactionProcessor with an explicitly sized worker pool.
+    ///
+    /// # Arguments
+    ///
+    /// * endpoint - the validator endpoint to connect to
+    /// * threads - the number of worker threads applying requests in parallel;
+    ///   clamped to at least one
+    pub fn new_with_threads(endpoint: &str, threads: usize) -> TransactionProcessor {
         TransactionProcessor {
             endpoint: String::from(endpoint),
             conn: ZmqMessageConnection::new(endpoint),
-            handlers: Vec::new()
+            handlers: Vec::new(),
+            max_workers: if threads == 0 { 1 } else { threads },
+            initial_reconnect_delay: Duration::from_millis(
+                DEFAULT_INITIAL_RECONNECT_DELAY),
+            max_reconnect_delay: Duration::from_millis(
+                DEFAULT_MAX_RECONNECT_DELAY),
+            sender: None,
+            protocol_version: ProtocolVersion::V1
         }
     }
 
+    /// Configures the reconnect backoff: the delay before the first reconnect
+    /// attempt and the cap the delay doubles up to. Full jitter is applied, so
+    /// each wait is a random point between zero and the current delay, and the
+    /// delay resets after each successful registration.
+    ///
+    /// # Arguments
+    ///
+    /// * initial - the base delay before the first reconnect attempt
+    /// * max - the ceiling the delay grows toward
+    pub fn set_reconnect_delays(&mut self, initial: Duration, max: Duration) {
+        self.initial_reconnect_delay = initial;
+        self.max_reconnect_delay = max;
+    }
+
+    /// Sets the protocol version this processor prefers when registering. The
+    /// validator may negotiate a different supported version in its response.
+    pub fn set_protocol_version(&mut self, version: ProtocolVersion) {
+        self.protocol_version = version;
+    }
+
+    /// Returns the protocol version currently in use: the one negotiated at
+    /// the last successful registration, or, before any registration, the
+    /// preferred version (V1 unless changed by `set_protocol_version`).
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.protocol_version
+    }
+
     /// Adds a transaction family handler
     ///
     /// # Arguments
     ///
     /// * handler - the handler to be added
-    pub fn add_handler(&mut self, handler: &'a TransactionHandler) {
+    pub fn add_handler(&mut self, handler: &'a (TransactionHandler + Sync)) {
         self.handlers.push(handler);
     }
 
+    /// Finds the registered handler for an incoming request by matching the
+    /// transaction header's family name and version against each handler's
+    /// `family_name()`/`family_versions()` -- the same tuple advertised in
+    /// `register()`. Returns `None` when no handler claims the family, so the
+    /// caller can reply with `INVALID_TRANSACTION` instead of running the
+    /// wrong family's logic.
+    ///
+    /// `namespaces()` is intentionally not part of the match: the
+    /// `TpProcessRequest` header carries only the family name and version, so
+    /// there is no namespace on the request to compare against. The validator
+    /// already routes by the namespaces advertised in `register()`, so a
+    /// request only reaches this processor for a family/version it registered.
+    fn find_handler(handlers: &[&'a (TransactionHandler + Sync)],
+                    request: &TpProcessRequest)
+        -> Option<&'a (TransactionHandler + Sync)> {
+        let header = request.get_header();
+        handlers.iter().find(|handler| {
+            handler.family_name() == header.get_family_name()
+                && handler.family_versions()
+                    .contains(&header.get_family_version().to_string())
+        }).copied()
+    }
+
     fn register(&mut self, mut sender: ZmqMessageSender) -> bool {
+        let codec = self.protocol_version.codec();
+        // Advertise this processor's preferred protocol version. The
+        // `TpRegisterRequest.protocol_version` field carries a single value, so
+        // only one version is communicated; the validator echoes the version it
+        // selected in the response, which we honor below.
+        let advertised = self.protocol_version.as_u32();
+        let mut negotiated = None;
+
         for handler in &self.handlers {
             for version in handler.family_versions() {
                 let mut request = TpRegisterRequest::new();
                 request.set_family(handler.family_name().clone());
                 request.set_version(version.clone());
                 request.set_namespaces(RepeatedField::from_vec(handler.namespaces().clone()));
+                request.set_protocol_version(advertised);
                 info!("sending TpRegisterRequest: {} {}",
                       &handler.family_name(),
                       &version);
@@ -97,7 +255,7 @@ impl<'a> TransactionProcessor<'a> {
                 let x : &[u8] = &serialized;
 
                 let mut future = match sender.send(
-                    Message_MessageType::TP_REGISTER_REQUEST,
+                    codec.register_request_type(),
                     &generate_correlation_id(),
                     x) {
                         Ok(fut) => fut,
@@ -108,40 +266,154 @@ impl<'a> TransactionProcessor<'a> {
                         }
                     };
 
-                // Absorb the TpRegisterResponse message
-                let _ = match future.get(){
-                    Ok(_) => (),
+                // Parse the TpRegisterResponse and honor the validator's
+                // selected protocol version.
+                let message = match future.get() {
+                    Ok(message) => message,
                     Err(err) => {
                         error!("Registration failed: {}", err.description());
                         // try reconnect
                         return false
                     }
                 };
+                let response: TpRegisterResponse = match protobuf::parse_from_bytes(
+                    &message.get_content()) {
+                    Ok(response) => response,
+                    Err(err) => {
+                        error!("Cannot parse TpRegisterResponse: {}", err.description());
+                        return false
+                    }
+                };
+                match ProtocolVersion::from_u32(response.get_protocol_version()) {
+                    Some(selected) => negotiated = Some(selected),
+                    None => {
+                        error!("Validator selected unsupported protocol version: {}",
+                               response.get_protocol_version());
+                        return false
+                    }
+                }
             }
         }
+
+        if let Some(selected) = negotiated {
+            info!("negotiated protocol version: {:?}", selected);
+            self.protocol_version = selected;
+        }
         true
     }
 
-    /// Connects the transaction processor to a validator and starts
-    /// listening for requests and routing them to an appropriate
-    /// transaction handler.
-    pub fn start(&mut self) {
-        let mut first_time = true;
-        let mut restart = true;
-        while restart {
-            info!("connecting to endpoint: {}", self.endpoint);
-            if first_time {
-                first_time = false;
-            } else {
-                self.conn = ZmqMessageConnection::new(&self.endpoint);
+    /// Applies a single request with the handler matching its family and
+    /// replies on `sender`, keyed by `correlation_id`. This runs on a worker
+    /// thread and is given only owned/shared data -- the handler references and
+    /// the negotiated protocol version -- rather than borrowing the whole
+    /// processor, so it never touches the receive loop; replies may complete
+    /// out of order but each carries its originating correlation id.
+    fn handle_process_request(handlers: &[&'a (TransactionHandler + Sync)],
+                              protocol_version: ProtocolVersion,
+                              request: &TpProcessRequest,
+                              correlation_id: &str, sender: &mut ZmqMessageSender) {
+        let mut context = TransactionContext::new(
+            request.get_context_id(), sender.clone());
+
+        let mut response = TpProcessResponse::new();
+        match Self::find_handler(handlers, request) {
+            Some(handler) => match handler.apply(request, &mut context) {
+                Ok(()) => {
+                    response.set_status(TpProcessResponse_Status::OK);
+                    info!("TP_PROCESS_REQUEST sending TpProcessResponse: OK");
+                },
+                Err(ApplyError::InvalidTransaction(msg)) => {
+                    response.set_status(
+                        TpProcessResponse_Status::INVALID_TRANSACTION);
+                    response.set_message(msg.clone());
+                    info!("TP_PROCESS_REQUEST sending TpProcessResponse: {}", msg);
+                },
+                Err(err) => {
+                    response.set_status(
+                        TpProcessResponse_Status::INTERNAL_ERROR);
+                    response.set_message(String::from(err.description()));
+                    info!("TP_PROCESS_REQUEST sending TpProcessResponse: {}",
+                          err.description());
+                }
+            },
+            None => {
+                let header = request.get_header();
+                let msg = format!(
+                    "no handler registered for family \"{}\" version \"{}\"",
+                    header.get_family_name(),
+                    header.get_family_version());
+                response.set_status(TpProcessResponse_Status::INVALID_TRANSACTION);
+                response.set_message(msg.clone());
+                info!("TP_PROCESS_REQUEST sending TpProcessResponse: {}", msg);
             }
-            let (mut sender, receiver) = self.conn.create();
-            // if registration is not succesful, retry
-            match self.register(sender.clone()) {
-                true => (),
-                false => continue
+        };
+
+        let serialized = match response.write_to_bytes() {
+            Ok(serialized) => serialized,
+            Err(err) => {
+                error!("Serialization failed: {}", err.description());
+                return
+            }
+        };
+
+        let x : &[u8] = &serialized;
+        match sender.reply(
+            protocol_version.codec().process_response_type(),
+            correlation_id,
+            x) {
+                Ok(_) => (),
+                Err(SendError::DisconnectedError) => error!("DisconnectedError"),
+                Err(SendError::TimeoutError) => error!("TimeoutError"),
+                Err(SendError::UnknownError) => error!("UnknownError")
+            };
+    }
+
+    /// Runs one connected session: spawns `max_workers` worker threads, each
+    /// draining its own work channel, then demuxes messages off the socket on
+    /// this thread, handing each TP_PROCESS_REQUEST to a worker. Returns
+    /// whether the caller should reconnect and restart.
+    fn run_session(&self, mut sender: ZmqMessageSender, receiver: MessageReceiver) -> bool {
+        // Shared, Send data the workers need, rather than borrowing `&self`
+        // (which is `!Sync` because it holds the `mpsc`-backed sender).
+        let handlers: &[&'a (TransactionHandler + Sync)] = &self.handlers;
+        let protocol_version = self.protocol_version;
+        let mut restart = true;
+
+        // One channel per worker, with the receive loop fanning requests out
+        // round-robin. This is driven by a std `mpsc` limitation, not a
+        // latency win: the `Receiver` is neither `Clone` nor `Sync`, so it
+        // cannot be shared across workers without wrapping it in a mutex. The
+        // tradeoff is worse tail latency -- a request assigned to a worker
+        // already busy in a slow `apply()` waits behind it even while other
+        // workers sit idle. A shared MPMC queue (e.g. crossbeam) would load
+        // balance better, but is avoided here to stay dependency-free.
+        let mut worker_txs = Vec::with_capacity(self.max_workers);
+        let mut worker_rxs = Vec::with_capacity(self.max_workers);
+        for _ in 0..self.max_workers {
+            let (tx, rx) = channel::<WorkItem>();
+            worker_txs.push(tx);
+            worker_rxs.push(rx);
+        }
+
+        thread::scope(|scope| {
+            for rx in worker_rxs {
+                scope.spawn(move || {
+                    // Each worker owns its own receiver and captures only the
+                    // handler references plus the negotiated protocol version,
+                    // so the closure is `Send` without sharing the processor.
+                    while let Ok(mut item) = rx.recv() {
+                        Self::handle_process_request(
+                            handlers, protocol_version,
+                            &item.request, &item.correlation_id,
+                            &mut item.sender);
+                    }
+                });
             }
 
+            // Incoming request types are matched through the negotiated
+            // version's codec.
+            let codec = protocol_version.codec();
+            let mut next_worker = 0;
             loop {
                 match receiver.recv() {
                     Ok(r) => {
@@ -161,7 +433,7 @@ impl<'a> TransactionProcessor<'a> {
                         info!("Message: {}", message.get_correlation_id());
 
                         match message.get_message_type() {
-                            Message_MessageType::TP_PROCESS_REQUEST => {
+                            t if t == codec.process_request_type() => {
                                 let request: TpProcessRequest = match protobuf::parse_from_bytes(
                                     &message.get_content()) {
                                     Ok(request) => request,
@@ -172,43 +444,25 @@ impl<'a> TransactionProcessor<'a> {
                                     }
                                 };
 
-                                let mut context = TransactionContext::new(
-                                    request.get_context_id(), sender.clone());
-
-                                let mut response = TpProcessResponse::new();
-                                match self.handlers[0].apply(&request, &mut context) {
-                                    Ok(()) => {
-                                        response.set_status(TpProcessResponse_Status::OK);
-                                        info!("TP_PROCESS_REQUEST sending TpProcessResponse: OK");
-                                    },
-                                    Err(ApplyError::InvalidTransaction(msg)) => {
-                                        response.set_status(
-                                            TpProcessResponse_Status::INVALID_TRANSACTION);
-                                        response.set_message(msg.clone());
-                                        info!("TP_PROCESS_REQUEST sending TpProcessResponse: {}",
-                                              msg);
-                                    },
-                                    Err(err) => {
-                                        response.set_status(
-                                            TpProcessResponse_Status::INTERNAL_ERROR);
-                                        response.set_message(String::from(err.description()));
-                                        info!("TP_PROCESS_REQUEST sending TpProcessResponse: {}",
-                                              err.description());
-                                    }
-                                };
-
-                                let serialized = match response.write_to_bytes()
-                                {
-                                    Ok(serialized) => serialized,
-                                    Err(err) => {
-                                        error!("Serialization failed: {}", err.description());
-                                        continue
-                                    }
+                                let item = WorkItem {
+                                    request,
+                                    correlation_id: String::from(message.get_correlation_id()),
+                                    sender: sender.clone()
                                 };
-
+                                if worker_txs[next_worker].send(item).is_err() {
+                                    error!("Worker pool unavailable; reconnecting");
+                                    break;
+                                }
+                                next_worker = (next_worker + 1) % worker_txs.len();
+                            },
+                            t if t == codec.ping_request_type() => {
+                                // Answer the validator's keepalive heartbeat so
+                                // the connection is not dropped as idle.
+                                let response = PingResponse::new();
+                                let serialized = response.write_to_bytes().unwrap();
                                 let x : &[u8] = &serialized;
                                 match sender.reply(
-                                    Message_MessageType::TP_PROCESS_RESPONSE,
+                                    self.protocol_version.codec().ping_response_type(),
                                     message.get_correlation_id(),
                                     x) {
                                         Ok(_) => (),
@@ -220,7 +474,7 @@ impl<'a> TransactionProcessor<'a> {
                                             error!("TimeoutError"),
                                         Err(SendError::UnknownError) => {
                                             restart = false;
-                                            println!("UnknownError");
+                                            error!("UnknownError");
                                             break
                                         }
                                     };
@@ -232,7 +486,7 @@ impl<'a> TransactionProcessor<'a> {
                             let serialized = response.write_to_bytes().unwrap();
                             let x : &[u8] = &serialized;
                             match sender.reply(
-                                Message_MessageType::TP_PROCESS_RESPONSE,
+                                self.protocol_version.codec().process_response_type(),
                                 message.get_correlation_id(),
                                 x){
                                     Ok(_) => (),
@@ -244,7 +498,7 @@ impl<'a> TransactionProcessor<'a> {
                                         error!("TimeoutError"),
                                     Err(SendError::UnknownError) => {
                                         restart = false;
-                                        println!("UnknownError");
+                                        error!("UnknownError");
                                         break
                                     }
                                 };
@@ -256,7 +510,229 @@ impl<'a> TransactionProcessor<'a> {
                     }
                 }
             }
+
+            // Dropping every worker sender closes each channel, so the workers
+            // finish their in-flight requests and then exit, letting the scope
+            // join before we reconnect.
+            drop(worker_txs);
+        });
+
+        sender.close();
+        restart
+    }
+
+    /// Sends a single `TpUnregisterRequest` so the validator can promptly drop
+    /// this processor from its routing table rather than waiting for a timeout.
+    /// A `TpUnregisterRequest` tears down the whole connection's registration
+    /// and carries no per-family field, so one request covers every family.
+    ///
+    /// This runs on the shutdown/`Drop` path, so it is best-effort and does not
+    /// block on the `TpUnregisterResponse`: against a down or unreachable
+    /// validator, waiting on each reply would stall teardown until the send
+    /// timed out. The returned future is dropped without being awaited.
+    fn unregister(&self, sender: &mut ZmqMessageSender) {
+        let request = TpUnregisterRequest::new();
+        info!("sending TpUnregisterRequest");
+        let serialized = match request.write_to_bytes() {
+            Ok(serialized) => serialized,
+            Err(err) => {
+                error!("Serialization failed: {}", err.description());
+                return
+            }
+        };
+        let x : &[u8] = &serialized;
+
+        if let Err(err) = sender.send(
+            self.protocol_version.codec().unregister_request_type(),
+            &generate_correlation_id(),
+            x) {
+            error!("Unregister failed: {}", err.description());
+        }
+    }
+
+    /// Unregisters every family from the validator and closes the sender. This
+    /// is also run on `Drop`, so a processor that falls out of scope leaves
+    /// the validator's routing table clean.
+    pub fn stop(&mut self) {
+        if let Some(mut sender) = self.sender.take() {
+            self.unregister(&mut sender);
             sender.close();
         }
     }
-}
\ No newline at end of file
+
+    /// Connects the transaction processor to a validator and starts
+    /// listening for requests and routing them to an appropriate
+    /// transaction handler.
+    pub fn start(&mut self) {
+        let mut first_time = true;
+        let mut restart = true;
+        let mut attempts = 0;
+        let mut backoff = Backoff::new(
+            self.initial_reconnect_delay, self.max_reconnect_delay);
+        while restart {
+            if first_time {
+                first_time = false;
+            } else {
+                // Back off before rebuilding the connection so a down or
+                // restarting validator is not hammered.
+                let delay = backoff.next_delay();
+                attempts += 1;
+                info!("reconnect attempt {} in {}ms",
+                      attempts,
+                      delay.as_secs() * 1000
+                          + (delay.subsec_nanos() / 1_000_000) as u64);
+                thread::sleep(delay);
+                self.conn = ZmqMessageConnection::new(&self.endpoint);
+            }
+            info!("connecting to endpoint: {}", self.endpoint);
+            let (sender, receiver) = self.conn.create();
+            // keep a sender for a clean TpUnregister on shutdown
+            self.sender = Some(sender.clone());
+            // if registration is not succesful, retry with backoff
+            match self.register(sender.clone()) {
+                true => {
+                    backoff.reset();
+                    attempts = 0;
+                },
+                false => continue
+            }
+
+            restart = self.run_session(sender, receiver);
+        }
+    }
+}
+
+impl<'a> Drop for TransactionProcessor<'a> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use messages::transaction::TransactionHeader;
+
+    /// A minimal handler that reports a fixed family/version and does nothing,
+    /// used to exercise dispatch without a validator.
+    struct StubHandler {
+        family: String,
+        versions: Vec<String>,
+    }
+
+    impl StubHandler {
+        fn new(family: &str, versions: &[&str]) -> StubHandler {
+            StubHandler {
+                family: String::from(family),
+                versions: versions.iter().map(|v| String::from(*v)).collect(),
+            }
+        }
+    }
+
+    impl TransactionHandler for StubHandler {
+        fn family_name(&self) -> String {
+            self.family.clone()
+        }
+        fn family_versions(&self) -> Vec<String> {
+            self.versions.clone()
+        }
+        fn namespaces(&self) -> Vec<String> {
+            Vec::new()
+        }
+        fn apply(&self, _request: &TpProcessRequest,
+                 _context: &mut TransactionContext) -> Result<(), ApplyError> {
+            Ok(())
+        }
+    }
+
+    /// Builds a request whose header carries `family`/`version`, the only
+    /// fields dispatch looks at.
+    fn request_for(family: &str, version: &str) -> TpProcessRequest {
+        let mut header = TransactionHeader::new();
+        header.set_family_name(String::from(family));
+        header.set_family_version(String::from(version));
+        let mut request = TpProcessRequest::new();
+        request.set_header(header);
+        request
+    }
+
+    #[test]
+    fn find_handler_matches_family_and_version() {
+        let handler = StubHandler::new("intkey", &["1.0"]);
+        let handlers: Vec<&(TransactionHandler + Sync)> = vec![&handler];
+        let request = request_for("intkey", "1.0");
+
+        let found = TransactionProcessor::find_handler(&handlers, &request);
+        assert_eq!(found.map(|h| h.family_name()), Some(String::from("intkey")));
+    }
+
+    #[test]
+    fn find_handler_selects_among_multiple() {
+        let intkey = StubHandler::new("intkey", &["1.0"]);
+        let xo = StubHandler::new("xo", &["1.0", "1.1"]);
+        let handlers: Vec<&(TransactionHandler + Sync)> = vec![&intkey, &xo];
+
+        let found = TransactionProcessor::find_handler(
+            &handlers, &request_for("xo", "1.1"));
+        assert_eq!(found.map(|h| h.family_name()), Some(String::from("xo")));
+    }
+
+    #[test]
+    fn find_handler_returns_none_when_unmatched() {
+        let handler = StubHandler::new("intkey", &["1.0"]);
+        let handlers: Vec<&(TransactionHandler + Sync)> = vec![&handler];
+
+        // Unknown family, and known family with an unregistered version: both
+        // miss, so the caller replies INVALID_TRANSACTION.
+        assert!(TransactionProcessor::find_handler(
+            &handlers, &request_for("sawtooth_settings", "1.0")).is_none());
+        assert!(TransactionProcessor::find_handler(
+            &handlers, &request_for("intkey", "2.0")).is_none());
+    }
+
+    #[test]
+    fn backoff_next_delay_within_base() {
+        let mut backoff = Backoff::new(
+            Duration::from_millis(100), Duration::from_millis(3000));
+        // The jittered delay is always a point in `[0, base]`, where `base` is
+        // the delay held before the call doubles it.
+        for _ in 0..64 {
+            let base = backoff.current;
+            assert!(backoff.next_delay() <= base);
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_and_caps_at_max() {
+        let mut backoff = Backoff::new(
+            Duration::from_millis(100), Duration::from_millis(400));
+        assert_eq!(backoff.current, Duration::from_millis(100));
+        backoff.next_delay();
+        assert_eq!(backoff.current, Duration::from_millis(200));
+        backoff.next_delay();
+        assert_eq!(backoff.current, Duration::from_millis(400));
+        // Held at the cap rather than growing past it.
+        backoff.next_delay();
+        assert_eq!(backoff.current, Duration::from_millis(400));
+    }
+
+    #[test]
+    fn backoff_reset_restores_initial() {
+        let mut backoff = Backoff::new(
+            Duration::from_millis(100), Duration::from_millis(3000));
+        backoff.next_delay();
+        backoff.next_delay();
+        backoff.reset();
+        assert_eq!(backoff.current, Duration::from_millis(100));
+    }
+
+    #[test]
+    fn backoff_does_not_overflow_near_max() {
+        // A near-`Duration::MAX` cap must not panic when the base doubles.
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::MAX);
+        for _ in 0..256 {
+            backoff.next_delay();
+        }
+    }
+}
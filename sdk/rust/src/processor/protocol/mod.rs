@@ -0,0 +1,100 @@
+/*
+ * Copyright 2017 Bitwise IO, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! Versioned validator wire protocols.
+//!
+//! The message types and serialization a processor uses are pinned behind a
+//! `Protocol` implementation selected per connection. The current behavior
+//! lives in [`v1`]; a future protocol can be added as a sibling module and
+//! advertised during registration without a breaking rewrite.
+
+pub mod v1;
+
+use messages::validator::Message_MessageType;
+
+/// The validator wire protocol versions this processor knows how to speak.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProtocolVersion {
+    V1,
+}
+
+impl ProtocolVersion {
+    /// The protocol versions this processor knows how to speak, newest first.
+    /// The wire format carries a single version at registration time, so a
+    /// processor advertises one preferred version rather than this whole list.
+    pub fn supported() -> Vec<ProtocolVersion> {
+        vec![ProtocolVersion::V1]
+    }
+
+    /// The wire number carried in `TpRegisterRequest`/`TpRegisterResponse`.
+    pub fn as_u32(&self) -> u32 {
+        match *self {
+            ProtocolVersion::V1 => 1,
+        }
+    }
+
+    /// Resolves the version negotiated by the validator, or `None` if it
+    /// selected a version this processor does not implement.
+    pub fn from_u32(value: u32) -> Option<ProtocolVersion> {
+        match value {
+            1 => Some(ProtocolVersion::V1),
+            _ => None,
+        }
+    }
+
+    /// Returns the codec that encodes and decodes messages for this version.
+    pub fn codec(&self) -> Box<Protocol> {
+        match *self {
+            ProtocolVersion::V1 => Box::new(v1::ProtocolV1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protocol_version_round_trips_through_u32() {
+        for version in ProtocolVersion::supported() {
+            assert_eq!(ProtocolVersion::from_u32(version.as_u32()), Some(version));
+        }
+    }
+
+    #[test]
+    fn protocol_version_rejects_unknown_wire_number() {
+        assert_eq!(ProtocolVersion::from_u32(0), None);
+        assert_eq!(ProtocolVersion::from_u32(99), None);
+    }
+}
+
+/// The version-specific message typing used when routing register,
+/// unregister, process and ping messages on and off the socket. State, event
+/// and receipt requests made through `TransactionContext` use the v1 message
+/// types directly and do not go through this trait. Each protocol version
+/// implements every method for its own message types.
+pub trait Protocol {
+    /// The version this codec implements.
+    fn version(&self) -> ProtocolVersion;
+
+    fn register_request_type(&self) -> Message_MessageType;
+    fn unregister_request_type(&self) -> Message_MessageType;
+    fn process_request_type(&self) -> Message_MessageType;
+    fn process_response_type(&self) -> Message_MessageType;
+    fn ping_request_type(&self) -> Message_MessageType;
+    fn ping_response_type(&self) -> Message_MessageType;
+}
@@ -0,0 +1,57 @@
+/*
+ * Copyright 2017 Bitwise IO, Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+
+//! The original validator wire protocol, bound to the `processor` protobufs.
+
+use messages::validator::Message_MessageType;
+
+use super::Protocol;
+use super::ProtocolVersion;
+
+/// Codec for [`ProtocolVersion::V1`], preserving the message typing the
+/// processor has always used.
+pub struct ProtocolV1;
+
+impl Protocol for ProtocolV1 {
+    fn version(&self) -> ProtocolVersion {
+        ProtocolVersion::V1
+    }
+
+    fn register_request_type(&self) -> Message_MessageType {
+        Message_MessageType::TP_REGISTER_REQUEST
+    }
+
+    fn unregister_request_type(&self) -> Message_MessageType {
+        Message_MessageType::TP_UNREGISTER_REQUEST
+    }
+
+    fn process_request_type(&self) -> Message_MessageType {
+        Message_MessageType::TP_PROCESS_REQUEST
+    }
+
+    fn process_response_type(&self) -> Message_MessageType {
+        Message_MessageType::TP_PROCESS_RESPONSE
+    }
+
+    fn ping_request_type(&self) -> Message_MessageType {
+        Message_MessageType::PING_REQUEST
+    }
+
+    fn ping_response_type(&self) -> Message_MessageType {
+        Message_MessageType::PING_RESPONSE
+    }
+}